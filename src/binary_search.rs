@@ -4,42 +4,105 @@
 //! Each time we search for an element in 1/2 of the search area. For example, we have 240_000
 //! elements in array. After first search step, in case we weren't so luck to find an element on
 //! the first step, we through away 120_000 possible variants (new search is 240_000 - 120_000).
+//!
+//! `src` must already be sorted by whatever ordering the chosen comparator induces - same
+//! precondition the standard library's `slice::binary_search` family has, and we mirror its API
+//! for the same reason: on a hit you get `Ok(index)`, and on a miss you get `Err(insertion_index)`,
+//! the position where the element could be inserted while keeping `src` sorted.
 
-// todo AddAssign for nums
-// todo type aliases to make it easy to read
-/// Important: src should be sorted.
-pub(super) fn binary_search(src: &[u32], element: u32) -> Option<usize> {
-    if src.is_empty() {
-        return None;
-    }
-    
-    // index of the lowest and highest elements of the `src`.
-    // such indexing helps us recognizing search area without actually mutating `src`.
-    let mut low = 0;
-    let mut high = src.len() - 1;
-    
-    // until search area is at least 1 element
-    while low != high { 
-        let mid = (low + high / 2) as usize;
-        let guess = src[mid];
-
-        if guess > element {
-            high = mid - 1;
-        }
+use std::cmp::Ordering;
 
-        if guess < element {
-            low = mid + 1;
-        }
+/// Important: `src` should be sorted.
+pub(super) fn binary_search<T: Ord>(src: &[T], element: &T) -> Result<usize, usize> {
+    binary_search_by(src, |probe| probe.cmp(element))
+}
+
+/// Same as `binary_search`, but keyed off `f(probe)` instead of comparing elements directly -
+/// handy when you want to search on one field of a struct that isn't itself `Ord`.
+pub(super) fn binary_search_by_key<T, B, F>(src: &[T], b: &B, mut f: F) -> Result<usize, usize>
+where
+    B: Ord,
+    F: FnMut(&T) -> B,
+{
+    binary_search_by(src, |probe| f(probe).cmp(b))
+}
+
+/// Same as `binary_search`, but takes a comparator instead of requiring `T: Ord` - lets callers
+/// search structs, reverse orderings, or anything else a plain `Ord` bound can't express.
+pub(super) fn binary_search_by<T, F>(src: &[T], mut cmp: F) -> Result<usize, usize>
+where
+    F: FnMut(&T) -> Ordering,
+{
+    // Half-open invariant: the element, if present, always lives somewhere in `[low, high)`.
+    // That lets the loop terminate simply when the range is empty (`low == high`), with no
+    // special-casing needed for the last element, and `mid` stays a valid index to inspect
+    // for as long as `low < high` holds.
+    let mut low = 0;
+    let mut high = src.len();
 
-        if guess == element {
-            return Some(mid);
+    while low < high {
+        // Written as `low + (high - low) / 2` rather than `(low + high) / 2` so it can't
+        // overflow on huge slices; it never underflows either, since `high >= low` always.
+        let mid = low + (high - low) / 2;
+        match cmp(&src[mid]) {
+            Ordering::Equal => return Ok(mid),
+            Ordering::Less => low = mid + 1,
+            Ordering::Greater => high = mid,
         }
     }
-    None
+
+    Err(low)
 }
 
 #[test]
 fn test_bin_search() {
     let src = (0..101).collect::<Vec<_>>();
-    assert_eq!(Some(50), binary_search(&src, 50));
+    assert_eq!(Ok(50), binary_search(&src, &50));
+}
+
+#[test]
+fn miss_returns_insertion_point() {
+    let src = vec![1, 3, 5, 7, 9];
+    assert_eq!(binary_search(&src, &0), Err(0));
+    assert_eq!(binary_search(&src, &4), Err(2));
+    assert_eq!(binary_search(&src, &10), Err(5));
+    assert_eq!(binary_search(&src, &5), Ok(2));
+}
+
+#[test]
+fn empty_slice() {
+    let src: Vec<i32> = vec![];
+    assert_eq!(binary_search(&src, &1), Err(0));
+}
+
+#[test]
+fn single_element() {
+    let src = vec![5];
+    assert_eq!(binary_search(&src, &5), Ok(0));
+    assert_eq!(binary_search(&src, &4), Err(0));
+    assert_eq!(binary_search(&src, &6), Err(1));
+}
+
+#[test]
+fn by_key_on_structs() {
+    #[derive(Debug, PartialEq)]
+    struct Item {
+        id: u32,
+        name: &'static str,
+    }
+
+    let src = vec![
+        Item { id: 1, name: "a" },
+        Item { id: 4, name: "b" },
+        Item { id: 9, name: "c" },
+    ];
+    assert_eq!(binary_search_by_key(&src, &4, |item| item.id), Ok(1));
+    assert_eq!(binary_search_by_key(&src, &5, |item| item.id), Err(2));
+}
+
+#[test]
+fn by_with_reverse_ordering() {
+    let src = vec![9, 7, 5, 3, 1];
+    assert_eq!(binary_search_by(&src, |probe| 3.cmp(probe)), Ok(3));
+    assert_eq!(binary_search_by(&src, |probe| 4.cmp(probe)), Err(3));
 }
@@ -1,9 +1,13 @@
 //! Hash table
 //!
 //! An idea is very simple. We put values in the array, but not just by pushing them. We have some smart algorithm for pushing,
-//! which requires having a way to compute the position of inserting value. The "way" is actually a hash function. Look at `HashTable::eval_index` method.
-//! There is a crucial contract in current implementation: size and capacity of buckets container are equal, however the "real length", which is amount
-//! of initialized (more preciously, non-zero or non-empty in our case) values differs from capacity.
+//! which requires having a way to compute the position of inserting value. The "way" is actually a hash function.
+//! Storage uses Robin Hood open addressing: entries live directly in a single flat slot array (`buckets::Buckets`) instead of
+//! being chained per-bucket. Each occupied slot also remembers its probe distance (DIB - "distance from initial bucket", i.e.
+//! how many slots away from `hash % cap` it ended up), and that's what "Robin Hood" refers to: while inserting, an element
+//! being probed steals a slot from a resident whose DIB is smaller ("steal from the rich, give to the poor"), which keeps
+//! probe-length variance low. Because DIB only grows as you walk forward from the ideal bucket, lookups can bail out early
+//! once the probe distance travelled exceeds the resident's own DIB.
 
 use std::hash::Hash;
 
@@ -46,13 +50,13 @@ impl<K: Hash + Eq, V> HashTable<K, V> {
     }
 
     fn needs_resize(&self) -> bool {
-        let real_len = self.buckets.len();
-        if real_len == 0 {
+        let cap = self.buckets.cap();
+        if cap == 0 {
             return true;
         }
-        // small occupancy reduces collision probability
-        let occupancy = real_len / self.buckets.cap();
-        occupancy > 7
+        // Load factor of 0.9, computed against the real entry count rather than the
+        // number of non-empty buckets, so it actually reflects how full the table is.
+        self.buckets.count_items() * 10 >= cap * 9
     }
 
     // If we need to resize bucket vector to prevent collision,
@@ -75,100 +79,161 @@ mod buckets {
 
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
-    use std::mem::replace;
+    use std::mem::{replace, swap};
 
-    // Not just V, but (K, V), because if we have faced collision and
-    // there's more than 1 element in vector, then we need to some how recognize
-    // desired value.
+    // An occupied slot. We keep the computed hash alongside the key so that re-probing
+    // during insert/resize never has to re-hash, and `dib` (distance from the ideal
+    // bucket `hash % cap`) is what lets Robin Hood decide who gets to keep a slot.
+    struct Entry<K, V> {
+        key: K,
+        value: V,
+        hash: usize,
+        dib: usize,
+    }
+
+    // Not just V, but (K, V), because if we have faced collision we need some
+    // way to recognize the desired value - hence entries carry their key.
     //
-    // Let's just use Vec<Vec> instead of Vec<LinkedList>.
-    pub(super) struct Buckets<K: Hash + Eq, V>(Vec<Vec<(K, V)>>);
+    // `len` tracks the real entry count; it can't be recovered cheaply from `slots`
+    // alone without a linear scan, so we maintain it ourselves.
+    pub(super) struct Buckets<K: Hash + Eq, V> {
+        slots: Vec<Option<Entry<K, V>>>,
+        len: usize,
+    }
 
     impl<K: Hash + Eq, V> Buckets<K, V> {
         pub(super) fn new() -> Self {
-            // Initializing like this `vec![Vec::new(), some_cap] needs K to implement Clone`,
-            // but by doing current zero initialization we enlarge our trait bound.
-            Self(Vec::new())
+            Self {
+                slots: Vec::new(),
+                len: 0,
+            }
         }
 
-        pub(super) fn remove(&mut self, key: &K) -> Option<V> {
-            let i = self.get_index(&key);
-            // Deleting if entry exists by finding its index in `self.0[i]` bucket and returning value
-            if let Some(pos) = self.get_pos_in_bucket(i, key) {
-                let (_, v) = self.0[i].swap_remove(pos);
-                return Some(v);
+        pub(super) fn insert(&mut self, key: K, value: V) -> Option<V> {
+            let hash = Self::hash_of(&key);
+            // If the key is already present, swap its value in place and keep its DIB -
+            // we're not moving it, just updating what it points to.
+            if let Some(i) = self.find_slot(&key, hash) {
+                let dib = self.slots[i].as_ref().expect("checked occupied above").dib;
+                let old = self.slots[i]
+                    .replace(Entry { key, value, hash, dib })
+                    .expect("checked occupied above");
+                return Some(old.value);
             }
+
+            self.robin_hood_insert(Entry { key, value, hash, dib: 0 });
+            self.len += 1;
             None
         }
 
-        pub(super) fn insert(&mut self, key: K, value: V) -> Option<V> {
-            let i = self.get_index(&key);
-            // Checking if entry exists by finding its index in `self.0[i]` bucket and returning value
-            if let Some(pos) = self.get_pos_in_bucket(i, &key) {
-                let (_, v) = replace(&mut self.0[i][pos], (key, value));
-                return Some(v);
+        // "Steal from the rich": probe forward from the entry's ideal bucket. At every
+        // occupied slot, if the resident's DIB is smaller than the DIB the entry being
+        // placed has already accumulated, swap them and keep inserting the displaced
+        // resident the same way. This bounds how far any single entry can end up from
+        // its ideal bucket.
+        fn robin_hood_insert(&mut self, mut entry: Entry<K, V>) {
+            let cap = self.slots.len();
+            let mut i = entry.hash % cap;
+            loop {
+                match &mut self.slots[i] {
+                    None => {
+                        self.slots[i] = Some(entry);
+                        return;
+                    }
+                    Some(resident) if resident.dib < entry.dib => swap(resident, &mut entry),
+                    Some(_) => {}
+                }
+                i = (i + 1) % cap;
+                entry.dib += 1;
             }
-            // Otherwise there isn't such key and we add a new one
-            self.0[i].push((key, value));
-            None
         }
 
-        fn get_pos_in_bucket(&self, bucket: usize, key: &K) -> Option<usize> {
-            self.0
-                .get(bucket)
-                .map(|bucket| bucket.iter().position(|(k, _)| k == key))
-                .flatten()
+        pub(super) fn remove(&mut self, key: &K) -> Option<V> {
+            let hash = Self::hash_of(key);
+            let i = self.find_slot(key, hash)?;
+            let removed = self.slots[i].take().expect("checked occupied above");
+            self.backward_shift(i);
+            self.len -= 1;
+            Some(removed.value)
+        }
+
+        // No tombstones needed: after clearing `hole`, pull the following entries back
+        // one slot at a time (lowering each one's DIB by one as it moves closer to its
+        // ideal bucket), stopping at the first empty slot or at an entry that's already
+        // sitting in its ideal bucket (DIB 0, so it has nothing to gain from shifting).
+        fn backward_shift(&mut self, mut hole: usize) {
+            let cap = self.slots.len();
+            loop {
+                let next = (hole + 1) % cap;
+                match &self.slots[next] {
+                    Some(entry) if entry.dib > 0 => {}
+                    _ => break,
+                }
+                let mut entry = self.slots[next].take().expect("checked Some above");
+                entry.dib -= 1;
+                self.slots[hole] = Some(entry);
+                hole = next;
+            }
         }
 
         pub(super) fn get<'a>(&'a self, key: &'a K) -> Option<&'a V> {
-            let i = self.get_index(key);
-            self.0
-                .get(i)
-                .map(|bucket| bucket.iter().find(|(k, _)| k == key))
-                .flatten()
-                .map(|(_, v)| v)
+            let hash = Self::hash_of(key);
+            let i = self.find_slot(key, hash)?;
+            self.slots[i].as_ref().map(|entry| &entry.value)
         }
 
-        pub(super) fn cap(&self) -> usize {
-            self.0.capacity()
+        // Walk forward from the key's ideal bucket. The Robin Hood invariant (DIB only
+        // increases while probing forward from the ideal bucket) means we can stop the
+        // moment a resident's DIB is less than the distance we've travelled: the key
+        // can't be stored any further out than that.
+        fn find_slot(&self, key: &K, hash: usize) -> Option<usize> {
+            let cap = self.slots.len();
+            if cap == 0 {
+                return None;
+            }
+            let mut i = hash % cap;
+            let mut dib = 0;
+            loop {
+                match &self.slots[i] {
+                    Some(entry) if entry.dib < dib => return None,
+                    Some(entry) if entry.hash == hash && &entry.key == key => return Some(i),
+                    Some(_) => {}
+                    None => return None,
+                }
+                i = (i + 1) % cap;
+                dib += 1;
+            }
         }
 
-        // This is strange at first glance. But the fact that we initialize bucket with default values by `resize_with(cap, || Vec::new())` at `Self::resize`
-        // means that we have `self.0.len() == self.0.cap()`. So the "real len" is an amount of non-empty buckets in `self.0` vector.
-        pub(super) fn len(&self) -> usize {
-            self.0.iter().filter(|l| !l.is_empty()).count()
+        pub(super) fn cap(&self) -> usize {
+            self.slots.len()
         }
 
-        // Amount of entries. Note: not `self.0.len()`, which is the same as cap in current implementation,
-        // nor the `Self::count_non_empty()`
+        // Amount of entries actually stored, as opposed to `self.slots.len()`, which is capacity.
         pub(super) fn count_items(&self) -> usize {
-            self.0.iter().fold(0, |acc, l| acc + l.len())
+            self.len
         }
 
         pub(super) fn resize(&mut self, cap: usize) {
-            let mut new_buckets = Vec::with_capacity(cap);
-            new_buckets.resize_with(cap, || Vec::new());
-
-            let old_buckets = replace(&mut self.0, new_buckets);
-            self.update_old_values(old_buckets);
-        }
-
-        fn update_old_values(&mut self, old_buckets: Vec<Vec<(K, V)>>) {
-            // Fill in with old entries, but with new indexes.
-            for bucket in old_buckets {
-                let (k, _) = bucket.get(0).expect("existing bucket can't be empty");
-                let new_index = self.get_index(k);
-                self.0[new_index] = bucket;
+            let new_slots = (0..cap).map(|_| None).collect();
+            let old_slots = replace(&mut self.slots, new_slots);
+            self.len = 0;
+            // Entries are re-inserted from scratch rather than copied verbatim, since a
+            // new capacity means new ideal buckets and therefore new DIBs.
+            for entry in old_slots.into_iter().flatten() {
+                self.robin_hood_insert(Entry {
+                    dib: 0,
+                    ..entry
+                });
+                self.len += 1;
             }
         }
 
-        // Just a standard way of getting index for the key
-        fn get_index(&self, key: &K) -> usize {
+        // Just a standard way of getting the hash for a key
+        fn hash_of(key: &K) -> usize {
             let mut h = DefaultHasher::new();
             key.hash(&mut h);
-            let hash = h.finish() as usize;
-            // This is very important! `hash mod array_size`. In current implementation array_size == len == cap.
-            hash % self.0.len()
+            h.finish() as usize
         }
     }
 }
@@ -190,3 +255,345 @@ fn simple() {
     ht.remove(&1);
     assert!(!ht.contains_key(&1));
 }
+
+#[test]
+fn robin_hood_forced_collisions_and_backward_shift() {
+    // Every key below hashes identically, so every insert after the first has to probe
+    // past however many entries are already there, and removal has to run a multi-entry
+    // `backward_shift` chain rather than incidentally hitting at most one. Since all keys
+    // share one ideal bucket, an incoming entry's DIB never exceeds a resident's, so this
+    // does not trigger Robin Hood's "steal from the rich" swap on insert.
+    struct CollidingKey(u32);
+
+    impl PartialEq for CollidingKey {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+    impl Eq for CollidingKey {}
+    impl Hash for CollidingKey {
+        fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+            0u32.hash(state);
+        }
+    }
+
+    let mut ht = HashTable::new();
+    for i in 0..20 {
+        ht.insert(CollidingKey(i), i);
+    }
+    assert_eq!(ht.len(), 20);
+    for i in 0..20 {
+        assert_eq!(ht.get(&CollidingKey(i)), Some(&i));
+    }
+
+    // Remove a contiguous run out of the middle of the collision chain, so
+    // `backward_shift` has to pull several subsequent entries back one at a time rather
+    // than just filling the hole from its immediate neighbour.
+    for i in (5..15).rev() {
+        assert_eq!(ht.remove(&CollidingKey(i)), Some(i));
+    }
+    assert_eq!(ht.len(), 10);
+    for i in 0..5 {
+        assert_eq!(ht.get(&CollidingKey(i)), Some(&i));
+    }
+    for i in 15..20 {
+        assert_eq!(ht.get(&CollidingKey(i)), Some(&i));
+    }
+    for i in 5..15 {
+        assert!(!ht.contains_key(&CollidingKey(i)));
+    }
+}
+
+/// Like `HashTable`, but `iter` walks entries in the order they were inserted, independent of
+/// where hashing happened to place them. It keeps the same O(1)-lookup hash index, but the
+/// index (`ordered_buckets::OrderedBuckets`) stores positions into a dense `entries` vector
+/// rather than owning the `(K, V)` pairs itself; `entries` is what iteration walks.
+pub(super) struct OrderedHashTable<K: Hash + Eq, V> {
+    entries: Vec<(K, V)>,
+    index: ordered_buckets::OrderedBuckets,
+}
+
+impl<K: Hash + Eq, V> OrderedHashTable<K, V> {
+    pub(super) fn new() -> Self {
+        OrderedHashTable {
+            entries: Vec::new(),
+            index: ordered_buckets::OrderedBuckets::new(),
+        }
+    }
+
+    pub(super) fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if self.needs_resize() {
+            self.resize();
+        }
+        let hash = ordered_buckets::OrderedBuckets::hash_of(&key);
+        if let Some(i) = self.index.find(&key, hash, &self.entries) {
+            return Some(std::mem::replace(&mut self.entries[i].1, value));
+        }
+        let entry_index = self.entries.len();
+        self.entries.push((key, value));
+        self.index.insert_index(entry_index, hash);
+        None
+    }
+
+    pub(super) fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub(super) fn get(&self, key: &K) -> Option<&V> {
+        let hash = ordered_buckets::OrderedBuckets::hash_of(key);
+        self.index
+            .find(key, hash, &self.entries)
+            .map(|i| &self.entries[i].1)
+    }
+
+    /// Removes `key` by swap-removing it out of `entries`, which is O(1) but - exactly like
+    /// `Vec::swap_remove` - changes iteration order: whatever entry used to be last now takes
+    /// the removed one's place. Use `shift_remove` when insertion order must survive removals.
+    pub(super) fn remove(&mut self, key: &K) -> Option<V> {
+        let hash = ordered_buckets::OrderedBuckets::hash_of(key);
+        let i = self.index.find(key, hash, &self.entries)?;
+        self.index.remove(hash, i);
+
+        let last = self.entries.len() - 1;
+        let (_, value) = self.entries.swap_remove(i);
+        if i != last {
+            // `entries[last]` just moved down into slot `i`; repoint the index entry that
+            // used to point at `last` so it points at `i` instead.
+            let moved_hash = ordered_buckets::OrderedBuckets::hash_of(&self.entries[i].0);
+            self.index.repoint(moved_hash, last, i);
+        }
+        Some(value)
+    }
+
+    /// Like `remove`, but shifts every later entry back one position instead of moving the
+    /// last entry into the hole, so insertion order is preserved across the removal. O(n).
+    pub(super) fn shift_remove(&mut self, key: &K) -> Option<V> {
+        let hash = ordered_buckets::OrderedBuckets::hash_of(key);
+        let i = self.index.find(key, hash, &self.entries)?;
+        self.index.remove(hash, i);
+
+        let (_, value) = self.entries.remove(i);
+        for moved_from in (i + 1)..=self.entries.len() {
+            let moved_hash = ordered_buckets::OrderedBuckets::hash_of(&self.entries[moved_from - 1].0);
+            self.index.repoint(moved_hash, moved_from, moved_from - 1);
+        }
+        Some(value)
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Walks entries in the order they were inserted, deterministically - unlike
+    /// `HashTable`/`Buckets`, whose order follows hashing.
+    pub(super) fn iter(&self) -> impl Iterator<Item = &(K, V)> {
+        self.entries.iter()
+    }
+
+    fn needs_resize(&self) -> bool {
+        let cap = self.index.cap();
+        if cap == 0 {
+            return true;
+        }
+        self.index.count_items() * 10 >= cap * 9
+    }
+
+    fn resize(&mut self) {
+        let cap = match self.index.cap() {
+            0 => INITIAL_LEN,
+            n => n * 2,
+        };
+        self.index.resize(cap, &self.entries);
+    }
+}
+
+mod ordered_buckets {
+    //! Hidden in mod just to control API.
+    //!
+    //! Same Robin Hood probing as `buckets::Buckets`, but a slot stores the *position* of its
+    //! entry in an external dense `entries` vector instead of owning the `(K, V)` pair itself.
+    //! That indirection is what lets `OrderedHashTable` iterate in insertion order: iteration
+    //! just walks `entries` directly and never looks at this table at all.
+
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use std::mem::swap;
+
+    struct Slot {
+        hash: usize,
+        dib: usize,
+        index: usize,
+    }
+
+    pub(super) struct OrderedBuckets {
+        slots: Vec<Option<Slot>>,
+        len: usize,
+    }
+
+    impl OrderedBuckets {
+        pub(super) fn new() -> Self {
+            Self {
+                slots: Vec::new(),
+                len: 0,
+            }
+        }
+
+        pub(super) fn hash_of<K: Hash>(key: &K) -> usize {
+            let mut h = DefaultHasher::new();
+            key.hash(&mut h);
+            h.finish() as usize
+        }
+
+        pub(super) fn cap(&self) -> usize {
+            self.slots.len()
+        }
+
+        pub(super) fn count_items(&self) -> usize {
+            self.len
+        }
+
+        // Resolves `key` to its position in `entries`, the same early-exit way
+        // `Buckets::find_slot` does: walk forward from the ideal bucket, stop the moment a
+        // resident's DIB is smaller than the distance travelled so far.
+        pub(super) fn find<K: Eq, V>(&self, key: &K, hash: usize, entries: &[(K, V)]) -> Option<usize> {
+            let cap = self.slots.len();
+            if cap == 0 {
+                return None;
+            }
+            let mut i = hash % cap;
+            let mut dib = 0;
+            loop {
+                match &self.slots[i] {
+                    Some(slot) if slot.dib < dib => return None,
+                    Some(slot) if slot.hash == hash && &entries[slot.index].0 == key => {
+                        return Some(slot.index)
+                    }
+                    Some(_) => {}
+                    None => return None,
+                }
+                i = (i + 1) % cap;
+                dib += 1;
+            }
+        }
+
+        pub(super) fn insert_index(&mut self, index: usize, hash: usize) {
+            self.robin_hood_insert(Slot { hash, dib: 0, index });
+            self.len += 1;
+        }
+
+        pub(super) fn remove(&mut self, hash: usize, entry_index: usize) {
+            let i = self.locate_by_index(hash, entry_index);
+            self.slots[i] = None;
+            self.backward_shift(i);
+            self.len -= 1;
+        }
+
+        // Repoints whichever slot tracks `old_index` to `new_index` instead, without touching
+        // its hash/DIB - used after `entries` reshuffles positions around a removal.
+        pub(super) fn repoint(&mut self, hash: usize, old_index: usize, new_index: usize) {
+            let i = self.locate_by_index(hash, old_index);
+            self.slots[i].as_mut().expect("checked Some above").index = new_index;
+        }
+
+        pub(super) fn resize<K: Hash, V>(&mut self, cap: usize, entries: &[(K, V)]) {
+            self.slots = (0..cap).map(|_| None).collect();
+            self.len = 0;
+            for (index, (key, _)) in entries.iter().enumerate() {
+                let hash = Self::hash_of(key);
+                self.insert_index(index, hash);
+            }
+        }
+
+        fn locate_by_index(&self, hash: usize, entry_index: usize) -> usize {
+            let cap = self.slots.len();
+            let mut i = hash % cap;
+            loop {
+                if let Some(slot) = &self.slots[i] {
+                    if slot.index == entry_index {
+                        return i;
+                    }
+                }
+                i = (i + 1) % cap;
+            }
+        }
+
+        fn robin_hood_insert(&mut self, mut slot: Slot) {
+            let cap = self.slots.len();
+            let mut i = slot.hash % cap;
+            loop {
+                match &mut self.slots[i] {
+                    None => {
+                        self.slots[i] = Some(slot);
+                        return;
+                    }
+                    Some(resident) if resident.dib < slot.dib => swap(resident, &mut slot),
+                    Some(_) => {}
+                }
+                i = (i + 1) % cap;
+                slot.dib += 1;
+            }
+        }
+
+        fn backward_shift(&mut self, mut hole: usize) {
+            let cap = self.slots.len();
+            loop {
+                let next = (hole + 1) % cap;
+                match &self.slots[next] {
+                    Some(slot) if slot.dib > 0 => {}
+                    _ => break,
+                }
+                let mut slot = self.slots[next].take().expect("checked Some above");
+                slot.dib -= 1;
+                self.slots[hole] = Some(slot);
+                hole = next;
+            }
+        }
+    }
+}
+
+#[test]
+fn ordered_iteration_follows_insertion_order() {
+    let mut ht = OrderedHashTable::new();
+    ht.insert("z", 1);
+    ht.insert("a", 2);
+    ht.insert("m", 3);
+    assert_eq!(
+        ht.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+        vec!["z", "a", "m"]
+    );
+
+    let prev = ht.insert("a", 20);
+    assert_eq!(prev, Some(2));
+    assert_eq!(
+        ht.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+        vec!["z", "a", "m"]
+    );
+}
+
+#[test]
+fn ordered_remove_variants() {
+    let mut ht = OrderedHashTable::new();
+    for (k, v) in [("a", 1), ("b", 2), ("c", 3), ("d", 4)] {
+        ht.insert(k, v);
+    }
+
+    // `remove` is swap_remove-based: "b" is replaced by whatever was last ("d").
+    assert_eq!(ht.remove(&"b"), Some(2));
+    assert_eq!(
+        ht.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+        vec!["a", "d", "c"]
+    );
+    assert!(!ht.contains_key(&"b"));
+    assert_eq!(ht.get(&"d"), Some(&4));
+    assert_eq!(ht.get(&"c"), Some(&3));
+
+    // `shift_remove` keeps the remaining entries in their original relative order.
+    assert_eq!(ht.shift_remove(&"a"), Some(1));
+    assert_eq!(
+        ht.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+        vec!["d", "c"]
+    );
+    assert_eq!(ht.get(&"d"), Some(&4));
+    assert_eq!(ht.get(&"c"), Some(&3));
+    assert_eq!(ht.len(), 2);
+}
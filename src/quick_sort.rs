@@ -1,8 +1,28 @@
 //! Quick sort
 //!
-//! Worst case - O(n^2). Average - O(n*log(n)). `O` constant time could vary, depending on
-//! pivot choice, That's the reason we get 2 different `O` values for the algorithm.
-//! Quick sort is implemented here using recursion.
+//! This used to be a plain quicksort with the pivot hard-coded to the middle element, which means
+//! an adversary could always pick an input that drives every partition to its worst case, O(n^2).
+//! It's now a small pdqsort-style hybrid that layers a few techniques on top of the same partition
+//! scheme to make that worst case effectively unreachable:
+//! 1) Subslices at or below `INSERTION_SORT_CUTOFF` are sorted with plain insertion sort, which
+//!    beats quicksort's overhead once the slice is that small.
+//! 2) The pivot is chosen by median-of-three for medium slices, or a "ninther" - the median of
+//!    three medians-of-three sampled from different thirds of the slice - once the slice reaches
+//!    `NINTHER_CUTOFF`. Both make it much harder to force a degenerate (one-sided) split.
+//! 3) A partition that swaps almost nothing is a sign the slice is already close to sorted, so
+//!    before recursing further we try a bounded insertion-sort pass; if it finishes within budget
+//!    we're done without ever touching the recursive case again.
+//! 4) A recursion-depth budget of roughly `2 * log2(len)` caps how deep plain partitioning may go.
+//!    If a branch blows through it - only possible on pathological inputs - it's handed off to
+//!    heapsort instead, which doesn't care about pivot choice and guarantees O(n log n).
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const INSERTION_SORT_CUTOFF: usize = 20;
+const NINTHER_CUTOFF: usize = 128;
+// If a partition swapped at most `len / NEARLY_SORTED_SWAP_RATIO` elements, it's worth trying
+// the bounded insertion-sort shortcut before recursing.
+const NEARLY_SORTED_SWAP_RATIO: usize = 8;
 
 pub(super) fn quick_sort<T: Ord>(src: &mut [T]) {
     // base case
@@ -14,51 +34,140 @@ pub(super) fn quick_sort<T: Ord>(src: &mut [T]) {
             }
         }
         // recursion case
-        _ => quick_sort_impl(src),
+        len => quick_sort_impl(src, 2 * log2_floor(len)),
     }
 }
 
-fn quick_sort_impl<T: Ord>(src: &mut [T]) {
-    // As it was stated, `O` varies in accordance to a chosen pivot value
-    // We saw lot's of implementations using first/last value as a pivot.
-    // Let's use the middle one as an example.
-    let pivot_index = src.len() / 2;
+fn quick_sort_impl<T: Ord>(src: &mut [T], depth_limit: usize) {
+    if src.len() <= INSERTION_SORT_CUTOFF {
+        insertion_sort(src);
+        return;
+    }
+
+    if depth_limit == 0 {
+        // We've recursed deeper than the `2 * log2(len)` budget allows, which only happens
+        // when partitioning keeps failing to make good progress (the adversarial case).
+        // Heapsort doesn't depend on pivot choice, so handing this branch to it converts
+        // the worst case into a guaranteed O(n log n).
+        heap_sort(src);
+        return;
+    }
+
+    let pivot_index = choose_pivot_index(src);
+    let (b, swaps) = partition(src, pivot_index);
 
-    // So here is partition. Partition is a core of quick sort. It's aim is to
-    // place all the values less than the pivot to the left of it
-    // and all the values greater than the pivot to the right of it.
-    //
-    // So `i` is an index of values less than the pivot, but `j`, otherwise, is an index of values greater than the pivot.
+    if swaps <= src.len() / NEARLY_SORTED_SWAP_RATIO {
+        // Few swaps means the slice was already close to sorted order. Try finishing it
+        // off with a single bounded insertion-sort pass - much cheaper than recursing
+        // further if that hunch is right. On failure it gives up without having broken
+        // the `b` split: insertion sort only ever swaps adjacent elements that are
+        // strictly out of order, and `partition` already guarantees everything in
+        // `src[..b]` is <= the pivot and everything in `src[b+1..]` is >=, so no element
+        // can cross the boundary at `b` either way. It's safe to fall through to the same
+        // halves the normal case below recurses into.
+        let bound = src.len() / 2;
+        if try_insertion_sort_bounded(src, bound) {
+            return;
+        }
+    }
+
+    let depth_limit = depth_limit - 1;
+    quick_sort_impl(&mut src[..b], depth_limit);
+    quick_sort_impl(&mut src[b + 1..], depth_limit);
+}
+
+// Below this many elements, sorting on the current thread beats the overhead of spawning
+// another task for it.
+const PARALLEL_SORT_CUTOFF: usize = 4096;
+
+/// Same algorithm as `quick_sort`, but once a partition's two halves (`[..b]` and `[b+1..]`)
+/// are large enough to be worth the task-spawn overhead, they're sorted concurrently instead
+/// of one after the other - the two halves never alias, so this is safe. Small slices fall
+/// back to the ordinary sequential `quick_sort` directly.
+///
+/// The number of OS threads alive at once is capped at roughly `available_parallelism()`:
+/// the cutoff above only bounds how small a *leaf* task gets before going sequential, it
+/// doesn't bound how many tasks end up running concurrently, so without this a large enough
+/// input would spawn a thread per recursive call above the cutoff - hundreds or thousands of
+/// them for multi-million-element inputs.
+pub(super) fn quick_sort_parallel<T: Ord + Send>(src: &mut [T]) {
+    if src.len() <= PARALLEL_SORT_CUTOFF {
+        quick_sort(src);
+        return;
+    }
+    let spawn_budget = AtomicUsize::new(
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1),
+    );
+    quick_sort_parallel_impl(src, 2 * log2_floor(src.len()), &spawn_budget);
+}
+
+fn quick_sort_parallel_impl<T: Ord + Send>(
+    src: &mut [T],
+    depth_limit: usize,
+    spawn_budget: &AtomicUsize,
+) {
+    if src.len() <= PARALLEL_SORT_CUTOFF || depth_limit == 0 {
+        quick_sort_impl(src, depth_limit);
+        return;
+    }
+
+    let pivot_index = choose_pivot_index(src);
+    let (b, _swaps) = partition(src, pivot_index);
+    let (left, rest) = src.split_at_mut(b);
+    // `rest[0]` is the pivot, already in its final position; only `rest[1..]` still needs sorting.
+    let right = &mut rest[1..];
+    let depth_limit = depth_limit - 1;
+
+    // Only spawn another OS thread while there's spare budget (roughly one per available
+    // core); once live tasks have used it all up, keep recursing on the current thread
+    // instead of spawning further, the same way a bounded thread pool would queue excess
+    // work rather than grow unboundedly.
+    let got_budget = spawn_budget
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |b| b.checked_sub(1))
+        .is_ok();
+    if got_budget {
+        std::thread::scope(|scope| {
+            scope.spawn(|| quick_sort_parallel_impl(left, depth_limit, spawn_budget));
+            quick_sort_parallel_impl(right, depth_limit, spawn_budget);
+        });
+        spawn_budget.fetch_add(1, Ordering::Relaxed);
+    } else {
+        quick_sort_parallel_impl(left, depth_limit, spawn_budget);
+        quick_sort_parallel_impl(right, depth_limit, spawn_budget);
+    }
+}
+
+// Partitions `src` around the element at `pivot_index`, returning the pivot's final position
+// together with how many elements were actually swapped out of place.
+//
+// So `i` is an index of values less than the pivot, but `j`, otherwise, is an index of values
+// greater than the pivot.
+fn partition<T: Ord>(src: &mut [T], pivot_index: usize) -> (usize, usize) {
     let mut i = 0;
     let mut j = src.len() - 1;
+    let mut swaps = 0;
     while i <= j {
-        // Using first or last element as a pivot makes a bit easier rust implementation, because
-        // you just go through `let a = src.split_first_mut()` or `let a = src.split_last_mut()` which makes easier
-        // 1) handling some test cases, 2) not getting pivot value in every loop (but we have to, because of mutable `swap` op in `else`).
-        // if you didn't get it, look at this: https://github.com/jonhoo/orst/blob/da6ba90195f94ec334f382b07498fc4e01795f20/src/quicksort.rs#L17-L42
+        // We re-read the pivot value on every iteration (rather than caching a reference)
+        // because `src.swap` below needs `src` mutably. The pivot itself never moves: it is
+        // neither greater nor less than itself, so it is never picked for a swap.
         let pivot = &src[pivot_index];
         if &src[i] <= pivot {
-            // that's fine, current value is in the right place
-            // ignore pivot, just go through it
-            //
-            // Never tries to get value out of `src` bound,
-            // because we iterate over src until i <= j, where j = [src.len(), src.len() - 1, ... , 0]
+            // that's fine, current value is in the right place, go through it
             i += 1;
         } else if &src[j] >= pivot {
-            // that's fine, current value is in the right place
-            // ignore pivot, just go through it
+            // that's fine, current value is in the right place, go through it
             if j == 0 {
-                // Going through pivot from right to left means,
-                // that we could reach the beginning of the `src`.
+                // Going through pivot from right to left means we could reach the
+                // beginning of `src`.
                 break;
             }
             j -= 1;
         } else {
-            // If src[i] > pivot and src[j] is less than pivot,
-            // it means we have found values with wrong positions.
-            // Swap them!
+            // src[i] > pivot and src[j] < pivot: found two values in the wrong halves, swap them.
             src.swap(i, j);
-            // and go further...
+            swaps += 1;
             i += 1;
             if j == 0 {
                 break;
@@ -67,41 +176,115 @@ fn quick_sort_impl<T: Ord>(src: &mut [T]) {
         }
     }
 
-    // That's the other core part of the quick sort.
-    // After partition we have an unsorted slice of values,
-    // where order of values has a significant attribute:
-    // we could place pivot in some place of slice, such that the slice will look like:
-    // [unsorted less | pivot | unsorted greater ].
-    //
-    // So what's the new index for pivot? Another great part of partition is that
-    // after it has "sorted" elements, `i` and `j` point to the right position for the pivot.
-    // For example, let's use `i` as pointer to the new valid pivot position.
-    //
-    // There could be 2 different cases for the new valid pivot position.
-    //
-    // First case is when we ended up processing values after `pivot_index`.
-    // As we could see from the `while i <= j`, we finish loop when this `i - 1 == j` (1) will be true.
-    // We know that `i` is an index of value less than pivot. It means that at the end of partition we have `i` pointing
-    // to the last value of `src`, which is less than pivot, so we can just swap positions between pivot and value under `i`.
-    // Seems to be right? But not. Due to (1) condition, after partition `i` points to the value,
-    // which is either greater than pivot or out of `src` bounds. So we should swap pivot element with `i-1` element.
-    //
-    // Second case is when we ended up processing values before `pivot_index`.
-    // This differs from the first case in a very crucial way. In first case went go out of the while loop,
-    // because `i` got increased to value greater than `j`. Here we go out of the while loop, because `j` got decreased to value less than `i`.
-    // In this case the last operation in the loop is the one on line 57. This actually means, that by the end of partition, `j + 1` points to the first
-    // element of `src` that is greater than pivot, Due to (1) condition, `j + 1 == i`, so we just swap element under `i` with pivot.
-    //
-    // There is no third case when we ended up on `i == j == pivot_index`, because
-    // 1) we always go through pivot, 2) we end up only when (1) condition is met.
-
+    // After the loop, everything in `src[..i]` is <= the pivot and everything in `src[i..]`
+    // is >= it. The pivot's final position depends on which part it sits in: if it is in
+    // the left part (`pivot_index < i`), it goes to `i - 1`, the last slot of that part;
+    // otherwise it goes to `i`, the first slot of the right part. Either swap keeps both
+    // parts' guarantees.
     let b = if i > pivot_index { i - 1 } else { i };
     src.swap(pivot_index, b);
+    (b, swaps)
+}
+
+// Picks the pivot index using median-of-three for slices below `NINTHER_CUTOFF`, and a
+// "ninther" - the median of three medians-of-three, each sampled from a different third of
+// the slice - for larger ones. Either way this is much harder for an adversary to defeat
+// than always picking a fixed position.
+fn choose_pivot_index<T: Ord>(src: &[T]) -> usize {
+    let len = src.len();
+    let mid = len / 2;
+    if len < NINTHER_CUTOFF {
+        median_of_three(src, 0, mid, len - 1)
+    } else {
+        let step = len / 8;
+        let m1 = median_of_three(src, 0, step, 2 * step);
+        let m2 = median_of_three(src, mid - step, mid, mid + step);
+        let m3 = median_of_three(src, len - 1 - 2 * step, len - 1 - step, len - 1);
+        median_of_three(src, m1, m2, m3)
+    }
+}
+
+fn median_of_three<T: Ord>(src: &[T], a: usize, b: usize, c: usize) -> usize {
+    if src[a] < src[b] {
+        if src[b] < src[c] {
+            b
+        } else if src[a] < src[c] {
+            c
+        } else {
+            a
+        }
+    } else if src[a] < src[c] {
+        a
+    } else if src[b] < src[c] {
+        c
+    } else {
+        b
+    }
+}
+
+// Attempts to finish sorting `src` via plain insertion sort, but gives up (returning `false`)
+// as soon as the total number of single-position shifts exceeds `bound`. A slice that really
+// is nearly sorted finishes cheaply; one that isn't gets abandoned early rather than paying
+// the O(n^2) cost. Shifts already applied before giving up are harmless: `src` is still some
+// permutation of its original elements either way.
+fn try_insertion_sort_bounded<T: Ord>(src: &mut [T], bound: usize) -> bool {
+    let mut shifts = 0;
+    for i in 1..src.len() {
+        let mut j = i;
+        while j > 0 && src[j - 1] > src[j] {
+            src.swap(j - 1, j);
+            j -= 1;
+            shifts += 1;
+            if shifts > bound {
+                return false;
+            }
+        }
+    }
+    true
+}
 
-    // recursively sort [unsorted less]
-    quick_sort(&mut src[..b]);
-    // and [unsorted greater]
-    quick_sort(&mut src[b + 1..]);
+fn insertion_sort<T: Ord>(src: &mut [T]) {
+    for i in 1..src.len() {
+        let mut j = i;
+        while j > 0 && src[j - 1] > src[j] {
+            src.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+// Standard binary-heap sort: build a max-heap in place, then repeatedly swap the max (the
+// root) to the end of the shrinking unsorted prefix and restore the heap property.
+fn heap_sort<T: Ord>(src: &mut [T]) {
+    let len = src.len();
+    for start in (0..len / 2).rev() {
+        sift_down(src, start, len);
+    }
+    for end in (1..len).rev() {
+        src.swap(0, end);
+        sift_down(src, 0, end);
+    }
+}
+
+fn sift_down<T: Ord>(src: &mut [T], mut root: usize, len: usize) {
+    loop {
+        let mut child = 2 * root + 1;
+        if child >= len {
+            break;
+        }
+        if child + 1 < len && src[child + 1] > src[child] {
+            child += 1;
+        }
+        if src[root] >= src[child] {
+            break;
+        }
+        src.swap(root, child);
+        root = child;
+    }
+}
+
+fn log2_floor(n: usize) -> usize {
+    (usize::BITS - n.leading_zeros() - 1) as usize
 }
 
 #[test]
@@ -157,3 +340,103 @@ fn simple() {
         assert_eq!(input, expected);
     }
 }
+
+#[test]
+fn large_adversarial_and_sorted_inputs() {
+    // A plain middle-pivot quicksort degrades to O(n^2) on inputs like this; pdqsort-style
+    // pivot selection plus the heapsort fallback should still sort it (and quickly).
+    let mut descending: Vec<i64> = (0..5000).rev().collect();
+    let mut expected: Vec<i64> = (0..5000).collect();
+    quick_sort(&mut descending);
+    assert_eq!(descending, expected);
+
+    // Already-sorted input should hit the nearly-sorted shortcut.
+    let mut sorted: Vec<i64> = (0..5000).collect();
+    quick_sort(&mut sorted);
+    assert_eq!(sorted, expected);
+
+    let mut organ_pipe: Vec<i64> = (0..2500).chain((0..2500).rev()).collect();
+    expected = organ_pipe.clone();
+    expected.sort();
+    quick_sort(&mut organ_pipe);
+    assert_eq!(organ_pipe, expected);
+}
+
+#[test]
+fn depth_exhaustion_falls_back_to_heap_sort() {
+    // Calling `quick_sort_impl` directly with `depth_limit == 0` forces the heapsort
+    // fallback unconditionally, regardless of how well-behaved a real pivot choice would
+    // have been for these inputs. That's the point: it verifies the fallback itself
+    // rather than relying on some input happening to exhaust the real recursion budget.
+    let mut with_duplicates = vec![5, 3, 5, 1, 5, 2, 4, 5, 0, 5, 3, 3, 9, 9, 1, 2, 8, 7, 6, 5, 4];
+    assert!(with_duplicates.len() > INSERTION_SORT_CUTOFF);
+    let mut expected = with_duplicates.clone();
+    expected.sort();
+    quick_sort_impl(&mut with_duplicates, 0);
+    assert_eq!(with_duplicates, expected);
+
+    // Strictly descending input of odd length (21), also past `INSERTION_SORT_CUTOFF`.
+    let mut odd_len: Vec<i32> = (0..21).rev().collect();
+    let mut expected_odd = odd_len.clone();
+    expected_odd.sort();
+    quick_sort_impl(&mut odd_len, 0);
+    assert_eq!(odd_len, expected_odd);
+}
+
+#[test]
+fn heap_sort_directly() {
+    let mut src = vec![4, 1, 3, 9, 7, 0, 2, 8, 6, 5, 5, 5];
+    let mut expected = src.clone();
+    expected.sort();
+    heap_sort(&mut src);
+    assert_eq!(src, expected);
+
+    let mut empty: Vec<i32> = vec![];
+    heap_sort(&mut empty);
+    assert_eq!(empty, Vec::<i32>::new());
+
+    let mut single = vec![42];
+    heap_sort(&mut single);
+    assert_eq!(single, vec![42]);
+}
+
+#[test]
+fn parallel_matches_sequential() {
+    // Same fixture inputs as `simple`, but here we only care that `quick_sort_parallel`
+    // agrees with `quick_sort`, not with a hand-written expected output.
+    let fixtures: Vec<Vec<i64>> = vec![
+        vec![9, 2, 3, 4, 1, 6, 8, 19, 20, 34],
+        vec![10, 80, 30, 70, 40, 50, 90],
+        vec![2, 3, 4, 5, 10, 1, 11],
+        vec![1, 2, 3, 4, 5, 6, 7, 8, 9],
+        vec![1, 5, 3, 4],
+        vec![1, 2, 3, 0, 5],
+        vec![1, 2, 3],
+        vec![3, 1, 2],
+        vec![2, 1, 3],
+        vec![6, 1, 7, 9, 3, 8, 2, 5, 4, 0],
+        vec![3, 2],
+        vec![8, 3, 7, 9, 6, 1, 9, 10],
+        vec![8, 2, 78, 892, 11, 0, 34],
+        vec![9, 03, 83, 9, 2, 0, 1, 65, 2, 822, 9, 11, 22, 3, 3, 3, 47],
+        vec![-6, 9, 0, 1, 17, 91, 0, 178],
+        vec![-3, -2, -1, -9, -5, -1, -19, -33],
+        vec![-5, -6, -7, 0, 0, 0, 0, -8, 1, 2, 3],
+    ];
+    for input in fixtures {
+        let mut sequential = input.clone();
+        quick_sort(&mut sequential);
+
+        let mut parallel = input;
+        quick_sort_parallel(&mut parallel);
+
+        assert_eq!(parallel, sequential);
+    }
+
+    // Large enough to actually cross `PARALLEL_SORT_CUTOFF` and spawn tasks.
+    let mut big: Vec<i64> = (0..20_000).rev().collect();
+    let mut expected = big.clone();
+    quick_sort(&mut expected);
+    quick_sort_parallel(&mut big);
+    assert_eq!(big, expected);
+}